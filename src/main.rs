@@ -1,32 +1,220 @@
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use image::imageops::FilterType;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use log::{error, info, warn, LevelFilter};
 use lopdf::Document;
 use rayon::prelude::*;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use simplelog::{ColorChoice, Config as LogConfig, TermLogger, TerminalMode};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::{Arc, Condvar, Mutex};
 use walkdir::WalkDir;
 
+/// Ghostscript `-dPDFSETTINGS` presets, from smallest/lowest fidelity to largest/highest fidelity.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum PdfQuality {
+    Screen,
+    Ebook,
+    Printer,
+    Prepress,
+}
+
+impl PdfQuality {
+    fn as_gs_arg(self) -> &'static str {
+        match self {
+            PdfQuality::Screen => "-dPDFSETTINGS=/screen",
+            PdfQuality::Ebook => "-dPDFSETTINGS=/ebook",
+            PdfQuality::Printer => "-dPDFSETTINGS=/printer",
+            PdfQuality::Prepress => "-dPDFSETTINGS=/prepress",
+        }
+    }
+}
+
+/// Raster image format used for the full-size and thumbnail page renders.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ImageFormatArg {
+    Webp,
+    Avif,
+    Jpeg,
+    Png,
+}
+
+impl ImageFormatArg {
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormatArg::Webp => "webp",
+            ImageFormatArg::Avif => "avif",
+            ImageFormatArg::Jpeg => "jpg",
+            ImageFormatArg::Png => "png",
+        }
+    }
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Splits, compresses and generates WebP images from PDFs.", long_about = None)]
 struct Args {
-    /// Input path (file or directory)
+    /// Input path (file or directory); required unless a subcommand is given
     #[arg(short, long)]
-    path: String,
+    path: Option<String>,
 
     /// Output root directory
     #[arg(short, long, default_value = "output")]
     output_dir: String,
+
+    /// Ghostscript compression preset used for `compress_pdf_file`
+    #[arg(long, value_enum, default_value_t = PdfQuality::Ebook)]
+    pdf_quality: PdfQuality,
+
+    /// DPI used when rendering pages to images via Ghostscript
+    #[arg(long, default_value_t = 100)]
+    render_dpi: u32,
+
+    /// Thumbnail width in pixels (height is scaled to preserve aspect ratio)
+    #[arg(long, default_value_t = 310)]
+    thumb_width: u32,
+
+    /// Output image format(s) for rendered pages; pass a comma-separated list to emit several
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "webp")]
+    image_format: Vec<ImageFormatArg>,
+
+    /// Quality (0-100) used for formats with lossy quality control (avif, jpeg)
+    #[arg(long, default_value_t = 80, value_parser = clap::value_parser!(u8).range(0..=100))]
+    image_quality: u8,
+
+    /// Reprocess every file even if a matching cache manifest is found
+    #[arg(long)]
+    force: bool,
+
+    /// Number of files to process in parallel (0 = let rayon pick based on available cores)
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+
+    /// Maximum number of Ghostscript processes running at once (0 = unlimited)
+    #[arg(long, default_value_t = 0)]
+    gs_concurrency: usize,
+
+    /// Logging verbosity: error, warn, info, debug or trace
+    #[arg(short = 'v', long = "log-level", default_value = "info")]
+    log_level: LevelFilter,
+
+    /// Run a pre-flight validation pass over discovered PDFs before processing
+    #[arg(long)]
+    validate: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Delete cache manifests from an output directory
+    ClearCache {
+        /// Output directory to clear manifests from
+        #[arg(short, long, default_value = "output")]
+        output_dir: String,
+    },
+}
+
+/// Settings that affect how a page is rendered to raster images, bundled together so
+/// `process_single_pdf` and `generate_images_from_pdf` don't grow one positional
+/// parameter per knob (and trip clippy's `too_many_arguments`).
+#[derive(Clone, Copy)]
+struct ImageOptions<'a> {
+    render_dpi: u32,
+    thumb_width: u32,
+    image_formats: &'a [ImageFormatArg],
+    image_quality: u8,
+}
+
+/// Settings that affect `process_single_pdf`'s output, for the same reason as
+/// `ImageOptions`.
+#[derive(Clone, Copy)]
+struct RenderOptions<'a> {
+    pdf_quality: PdfQuality,
+    force: bool,
+    image: ImageOptions<'a>,
+}
+
+/// Cache manifest recorded per input PDF, used to skip already-generated output.
+#[derive(Serialize, Deserialize)]
+struct CacheManifest {
+    hash: String,
+    produced_files: Vec<String>,
+}
+
+/// A counting semaphore that caps how many Ghostscript processes run at once,
+/// independent of the file-level (rayon) parallelism. A cap of 0 means unlimited.
+struct GsLimiter {
+    cap: usize,
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+impl GsLimiter {
+    fn new(cap: usize) -> Self {
+        GsLimiter {
+            cap,
+            available: Mutex::new(cap),
+            released: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) -> GsPermit<'_> {
+        if self.cap != 0 {
+            let mut available = self.available.lock().unwrap();
+            while *available == 0 {
+                available = self.released.wait(available).unwrap();
+            }
+            *available -= 1;
+        }
+        GsPermit { limiter: self }
+    }
+}
+
+struct GsPermit<'a> {
+    limiter: &'a GsLimiter,
+}
+
+impl Drop for GsPermit<'_> {
+    fn drop(&mut self) {
+        if self.limiter.cap != 0 {
+            let mut available = self.limiter.available.lock().unwrap();
+            *available += 1;
+            self.limiter.released.notify_one();
+        }
+    }
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
+    TermLogger::init(
+        args.log_level,
+        LogConfig::default(),
+        TerminalMode::Mixed,
+        ColorChoice::Auto,
+    )
+    .context("Failed to initialize logger")?;
+
+    if let Some(Commands::ClearCache { output_dir }) = &args.command {
+        return clear_cache(output_dir);
+    }
+
+    if args.path.is_none() {
+        anyhow::bail!("--path is required unless a subcommand (e.g. clear-cache) is given");
+    }
+
     // File Discovery
-    let search_path = PathBuf::from(&args.path);
+    let path = args
+        .path
+        .clone()
+        .expect("path is required unless a subcommand is given");
+    let search_path = PathBuf::from(&path);
 
     if !search_path.exists() {
         anyhow::bail!("Error: Input path not found: {:?}", search_path);
@@ -39,7 +227,7 @@ fn main() -> Result<()> {
             .into_iter()
             .filter_map(|e| e.ok())
         {
-            if entry.path().extension().map_or(false, |ext| ext == "pdf") {
+            if entry.path().extension().is_some_and(|ext| ext == "pdf") {
                 pdf_files.push(entry.path().to_path_buf());
             }
         }
@@ -48,33 +236,203 @@ fn main() -> Result<()> {
     }
 
     if pdf_files.is_empty() {
-        println!("No PDF files found in {:?}", search_path);
+        info!("No PDF files found in {:?}", search_path);
         return Ok(());
     }
 
-    println!("Found {} files to process.", pdf_files.len());
-    println!("Output directory: {}", args.output_dir);
+    info!("Found {} files to process.", pdf_files.len());
+    info!("Output directory: {}", args.output_dir);
+
+    if args.validate {
+        let skip: std::collections::HashSet<PathBuf> =
+            run_preflight_validation(&pdf_files).into_iter().collect();
+        if !skip.is_empty() {
+            info!(
+                "Skipping {} broken file(s) flagged by pre-flight validation.",
+                skip.len()
+            );
+            pdf_files.retain(|p| !skip.contains(p));
+        }
+    }
+
+    if pdf_files.is_empty() {
+        info!("No healthy PDF files left to process after validation.");
+        return Ok(());
+    }
 
     let has_gs = check_ghostscript().is_ok();
     if !has_gs {
-        println!("⚠️  Ghostscript not found. Compression and Image generation will be skipped.");
-        println!("   To enable, place 'gs' (Linux) or 'gswin32c.exe' (Windows) in this folder.");
+        warn!("Ghostscript not found. Compression and Image generation will be skipped.");
+        warn!("To enable, place 'gs' (Linux) or 'gswin32c.exe' (Windows) in this folder.");
     } else {
-        println!("✅ Ghostscript detected. Compression and Images enabled.");
+        info!("Ghostscript detected. Compression and Images enabled.");
     }
 
     // Process in parallel
     let output_root = PathBuf::from(&args.output_dir);
+    let gs_limiter = Arc::new(GsLimiter::new(args.gs_concurrency));
+    // Every per-file progress bar is attached to this so pb.suspend()/multi.suspend()
+    // hides all of them at once, keeping log lines from interleaving with any bar.
+    let multi_progress = MultiProgress::new();
 
-    pdf_files.par_iter().for_each(|pdf_path| {
-        if let Err(e) = process_single_pdf(pdf_path, &output_root, has_gs) {
-            eprintln!("Failed to process {:?}: {}", pdf_path, e);
-        }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build()
+        .context("Failed to build worker thread pool")?;
+
+    let render_options = RenderOptions {
+        pdf_quality: args.pdf_quality,
+        force: args.force,
+        image: ImageOptions {
+            render_dpi: args.render_dpi,
+            thumb_width: args.thumb_width,
+            image_formats: &args.image_format,
+            image_quality: args.image_quality,
+        },
+    };
+
+    let results: Vec<FileProcessResult> = pool.install(|| {
+        pdf_files
+            .par_iter()
+            .filter_map(|pdf_path| {
+                match process_single_pdf(
+                    pdf_path,
+                    &output_root,
+                    has_gs,
+                    &render_options,
+                    &gs_limiter,
+                    &multi_progress,
+                ) {
+                    Ok(result) => Some(result),
+                    Err(e) => {
+                        multi_progress
+                            .suspend(|| error!("Failed to process {:?}: {}", pdf_path, e));
+                        None
+                    }
+                }
+            })
+            .collect()
     });
 
+    let failed_files: Vec<&FileProcessResult> = results
+        .iter()
+        .filter(|r| !r.failed_pages.is_empty())
+        .collect();
+
+    if !failed_files.is_empty() {
+        multi_progress.suspend(|| error!("--- Page failures ---"));
+        for result in failed_files {
+            let pages: Vec<String> = result
+                .failed_pages
+                .iter()
+                .map(|p| format!("{} ({})", p.page, p.message))
+                .collect();
+            multi_progress.suspend(|| {
+                error!(
+                    "file {}: pages {} failed",
+                    result.file.display(),
+                    pages.join(", ")
+                )
+            });
+        }
+    }
+
     Ok(())
 }
 
+/// A page whose split/compress/render step panicked, or whose split or PDF copy
+/// returned an error. Ghostscript compression errors (handled by copying the split
+/// PDF) and image-generation errors are not recorded here.
+struct PageError {
+    page: u32,
+    message: String,
+}
+
+/// Outcome of processing one input PDF: which pages (if any) failed.
+struct FileProcessResult {
+    file: PathBuf,
+    failed_pages: Vec<PageError>,
+}
+
+/// Result of opening one candidate PDF during the `--validate` pre-flight pass.
+struct PdfValidation {
+    file: PathBuf,
+    page_count: usize,
+    encrypted: bool,
+    error: Option<String>,
+}
+
+impl PdfValidation {
+    fn is_healthy(&self) -> bool {
+        self.error.is_none() && self.page_count > 0
+    }
+}
+
+/// Open every discovered PDF up front with `lopdf::Document::load`, checking for
+/// parse failures, zero-page documents and encryption, and print a summary before
+/// any splitting/compression/rendering work begins. Returns the paths that should
+/// be skipped (unparsable or zero-page) so the caller doesn't feed them into
+/// `process_single_pdf` only to fail there with a scattered mid-run error. Encrypted
+/// but otherwise-healthy documents are only warned about, not skipped, since lopdf
+/// can often still open permission-only-encrypted PDFs fine.
+fn run_preflight_validation(pdf_files: &[PathBuf]) -> Vec<PathBuf> {
+    let reports: Vec<PdfValidation> = pdf_files
+        .par_iter()
+        .map(|path| match Document::load(path) {
+            Ok(doc) => {
+                let page_count = doc.get_pages().len();
+                let encrypted = doc.trailer.get(b"Encrypt").is_ok();
+                let error = if page_count == 0 {
+                    Some("no pages found".to_string())
+                } else {
+                    None
+                };
+                PdfValidation {
+                    file: path.clone(),
+                    page_count,
+                    encrypted,
+                    error,
+                }
+            }
+            Err(e) => PdfValidation {
+                file: path.clone(),
+                page_count: 0,
+                encrypted: false,
+                error: Some(e.to_string()),
+            },
+        })
+        .collect();
+
+    let healthy_count = reports.iter().filter(|r| r.is_healthy()).count();
+    let broken: Vec<&PdfValidation> = reports.iter().filter(|r| !r.is_healthy()).collect();
+    let encrypted: Vec<&PdfValidation> = reports.iter().filter(|r| r.encrypted).collect();
+
+    info!(
+        "Pre-flight validation: {} healthy, {} broken, {} encrypted (of {} total)",
+        healthy_count,
+        broken.len(),
+        encrypted.len(),
+        reports.len()
+    );
+
+    for r in &broken {
+        error!(
+            "broken: {:?}: {}",
+            r.file,
+            r.error.as_deref().unwrap_or("unknown error")
+        );
+    }
+    for r in &encrypted {
+        warn!("encrypted: {:?}", r.file);
+    }
+
+    reports
+        .iter()
+        .filter(|r| !r.is_healthy())
+        .map(|r| r.file.clone())
+        .collect()
+}
+
 fn check_ghostscript() -> Result<()> {
     let gs_bin = get_gs_binary();
     Command::new(gs_bin)
@@ -102,7 +460,14 @@ fn get_gs_binary() -> String {
     }
 }
 
-fn process_single_pdf(file_path: &Path, output_root: &Path, has_gs: bool) -> Result<()> {
+fn process_single_pdf(
+    file_path: &Path,
+    output_root: &Path,
+    has_gs: bool,
+    opts: &RenderOptions,
+    gs_limiter: &GsLimiter,
+    multi_progress: &MultiProgress,
+) -> Result<FileProcessResult> {
     let filename = file_path.file_stem().unwrap().to_string_lossy();
     // let filename_full = file_path.file_name().unwrap().to_string_lossy();
 
@@ -142,6 +507,25 @@ fn process_single_pdf(file_path: &Path, output_root: &Path, has_gs: bool) -> Res
         fs::create_dir_all(&output_dir)?;
     }
 
+    // --- Cache check ---
+    let cache_hash = compute_cache_hash(
+        file_path,
+        opts.pdf_quality,
+        opts.image.render_dpi,
+        opts.image.thumb_width,
+        opts.image.image_formats,
+        opts.image.image_quality,
+    )?;
+    let manifest_path = output_dir.join(format!("{}.manifest.json", filename));
+
+    if !opts.force && manifest_is_fresh(&output_dir, &manifest_path, &cache_hash) {
+        info!("Skipping {} (unchanged, cached)", filename);
+        return Ok(FileProcessResult {
+            file: file_path.to_path_buf(),
+            failed_pages: Vec::new(),
+        });
+    }
+
     // --- Load PDF ---
     let mut doc = Document::load(file_path).context("Failed to load PDF")?;
     doc.renumber_objects();
@@ -149,12 +533,15 @@ fn process_single_pdf(file_path: &Path, output_root: &Path, has_gs: bool) -> Res
     let total_pages = pages.len();
 
     if total_pages == 0 {
-        return Ok(());
+        return Ok(FileProcessResult {
+            file: file_path.to_path_buf(),
+            failed_pages: Vec::new(),
+        });
     }
 
     let limit = std::cmp::min(total_pages, 30);
 
-    let pb = ProgressBar::new(limit as u64);
+    let pb = multi_progress.add(ProgressBar::new(limit as u64));
     pb.set_style(
         ProgressStyle::default_bar()
             .template("{msg} {spinner:.green} [{bar:40.cyan/blue}] {pos}/{len}")?
@@ -162,52 +549,198 @@ fn process_single_pdf(file_path: &Path, output_root: &Path, has_gs: bool) -> Res
     );
     pb.set_message(format!("{}", filename));
 
+    let mut failed_pages: Vec<PageError> = Vec::new();
+
     for i in 1..=limit {
-        let p_str = format!("{:0>2}", i);
-        let pdf_out_path = output_dir.join(format!("{}.pdf", p_str));
-        let pdf_compress_path = output_dir.join(format!("{}_compress.pdf", p_str));
-        let img_full_path = output_dir.join(format!("{}.webp", p_str));
-        let img_thumb_path = output_dir.join(format!("{}_thumb.webp", p_str));
-
-        // 1. Split
-        let mut new_doc = doc.clone();
-        let pages_to_delete: Vec<u32> = (1..=total_pages as u32)
-            .filter(|&p| p != i as u32)
-            .collect();
-        new_doc.delete_pages(&pages_to_delete);
-        new_doc.prune_objects();
-        new_doc.save(&pdf_out_path)?;
+        let page_body = std::panic::AssertUnwindSafe(|| -> Result<()> {
+            let p_str = format!("{:0>2}", i);
+            let pdf_out_path = output_dir.join(format!("{}.pdf", p_str));
+            let pdf_compress_path = output_dir.join(format!("{}_compress.pdf", p_str));
+
+            // 1. Split
+            let mut new_doc = doc.clone();
+            let pages_to_delete: Vec<u32> = (1..=total_pages as u32)
+                .filter(|&p| p != i as u32)
+                .collect();
+            new_doc.delete_pages(&pages_to_delete);
+            new_doc.prune_objects();
+            new_doc.save(&pdf_out_path)?;
 
-        if has_gs {
-            // 2. Compress (Ghostscript)
-            if let Err(_) = compress_pdf_file(&pdf_out_path, &pdf_compress_path) {
+            if has_gs {
+                // 2. Compress (Ghostscript)
+                if compress_pdf_file(
+                    &pdf_out_path,
+                    &pdf_compress_path,
+                    opts.pdf_quality,
+                    gs_limiter,
+                )
+                .is_err()
+                {
+                    fs::copy(&pdf_out_path, &pdf_compress_path)?;
+                }
+
+                // 3. Generate Images (GS + Rust)
+                // Use the COMPRESSED pdf for rendering as it's smaller and faster to read
+                if let Err(_e) = generate_images_from_pdf(
+                    &pdf_compress_path,
+                    &output_dir,
+                    &p_str,
+                    &opts.image,
+                    gs_limiter,
+                ) {
+                    // Image generation is best-effort; the split/compressed PDF still counts as a success.
+                }
+            } else {
                 fs::copy(&pdf_out_path, &pdf_compress_path)?;
             }
 
-            // 3. Generate Images (GS + Rust)
-            // Use the COMPRESSED pdf for rendering as it's smaller and faster to read
-            if let Err(_e) =
-                generate_images_from_pdf(&pdf_compress_path, &img_full_path, &img_thumb_path)
-            {
-                // eprintln!("Img error: {}", _e);
+            Ok(())
+        });
+
+        match std::panic::catch_unwind(page_body) {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                pb.suspend(|| warn!("{} page {}: {}", filename, i, e));
+                failed_pages.push(PageError {
+                    page: i as u32,
+                    message: e.to_string(),
+                });
+            }
+            Err(panic_payload) => {
+                let message = panic_message(&panic_payload);
+                pb.suspend(|| warn!("{} page {}: {}", filename, i, message));
+                failed_pages.push(PageError {
+                    page: i as u32,
+                    message,
+                });
             }
-        } else {
-            fs::copy(&pdf_out_path, &pdf_compress_path)?;
         }
 
         pb.inc(1);
     }
     pb.finish();
 
+    if failed_pages.is_empty() {
+        let produced_files: Vec<String> = (1..=limit)
+            .flat_map(|i| {
+                let p_str = format!("{:0>2}", i);
+                let mut files = vec![format!("{}.pdf", p_str), format!("{}_compress.pdf", p_str)];
+                for fmt in opts.image.image_formats {
+                    files.push(format!("{}.{}", p_str, fmt.extension()));
+                    files.push(format!("{}_thumb.{}", p_str, fmt.extension()));
+                }
+                files
+            })
+            .collect();
+        write_cache_manifest(&manifest_path, &cache_hash, produced_files)?;
+    }
+
+    Ok(FileProcessResult {
+        file: file_path.to_path_buf(),
+        failed_pages,
+    })
+}
+
+/// Hash the input PDF's bytes together with the settings that affect its output,
+/// so a manifest written under one quality/dpi/thumb-width combination is not
+/// mistaken for a match under another.
+fn compute_cache_hash(
+    file_path: &Path,
+    pdf_quality: PdfQuality,
+    render_dpi: u32,
+    thumb_width: u32,
+    image_formats: &[ImageFormatArg],
+    image_quality: u8,
+) -> Result<String> {
+    let bytes = fs::read(file_path).context("Failed to read PDF for cache hashing")?;
+    let mut hasher = Sha256::new();
+    hasher.update(&bytes);
+    hasher.update(
+        format!(
+            "{:?}|{}|{}|{:?}|{}",
+            pdf_quality, render_dpi, thumb_width, image_formats, image_quality
+        )
+        .as_bytes(),
+    );
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Returns true if a manifest exists at `manifest_path`, matches `hash`, and every
+/// file it recorded as produced is still present under `output_dir`.
+fn manifest_is_fresh(output_dir: &Path, manifest_path: &Path, hash: &str) -> bool {
+    let Ok(data) = fs::read_to_string(manifest_path) else {
+        return false;
+    };
+    let Ok(manifest) = serde_json::from_str::<CacheManifest>(&data) else {
+        return false;
+    };
+    manifest.hash == hash
+        && manifest
+            .produced_files
+            .iter()
+            .all(|f| output_dir.join(f).exists())
+}
+
+fn write_cache_manifest(
+    manifest_path: &Path,
+    hash: &str,
+    produced_files: Vec<String>,
+) -> Result<()> {
+    let manifest = CacheManifest {
+        hash: hash.to_string(),
+        produced_files,
+    };
+    let data = serde_json::to_string_pretty(&manifest)?;
+    fs::write(manifest_path, data)?;
+    Ok(())
+}
+
+/// Delete all cache manifests under `output_dir` (the `clear-cache` subcommand).
+fn clear_cache(output_dir: &str) -> Result<()> {
+    let root = PathBuf::from(output_dir);
+    if !root.exists() {
+        warn!(
+            "Output directory {:?} does not exist; nothing to clear.",
+            root
+        );
+        return Ok(());
+    }
+
+    let mut removed = 0;
+    for entry in WalkDir::new(&root).into_iter().filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy();
+        if name.ends_with(".manifest.json") {
+            fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+    }
+    info!("Removed {} cache manifest(s) from {:?}", removed, root);
     Ok(())
 }
 
-fn compress_pdf_file(input: &Path, output: &Path) -> Result<()> {
+/// Extract a human-readable message from a caught panic payload.
+fn panic_message(payload: &Box<dyn std::any::Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+fn compress_pdf_file(
+    input: &Path,
+    output: &Path,
+    pdf_quality: PdfQuality,
+    gs_limiter: &GsLimiter,
+) -> Result<()> {
     let gs_bin = get_gs_binary();
+    let _permit = gs_limiter.acquire();
     let status = Command::new(gs_bin)
         .arg("-sDEVICE=pdfwrite")
         .arg("-dCompatibilityLevel=1.4")
-        .arg("-dPDFSETTINGS=/ebook")
+        .arg(pdf_quality.as_gs_arg())
         .arg("-dNOPAUSE")
         .arg("-dQUIET")
         .arg("-dBATCH")
@@ -215,22 +748,30 @@ fn compress_pdf_file(input: &Path, output: &Path) -> Result<()> {
         .arg(input)
         .status()?;
 
+    drop(_permit);
+
     if !status.success() {
         anyhow::bail!("GS failed");
     }
     Ok(())
 }
 
-fn generate_images_from_pdf(pdf_path: &Path, full_out: &Path, thumb_out: &Path) -> Result<()> {
+fn generate_images_from_pdf(
+    pdf_path: &Path,
+    output_dir: &Path,
+    page_prefix: &str,
+    opts: &ImageOptions,
+    gs_limiter: &GsLimiter,
+) -> Result<()> {
     let gs_bin = get_gs_binary();
     // Temp PNG file in the same dir
     let temp_png = pdf_path.with_extension("temp.png");
 
     // A. Render to PNG using GS
-    // Optimized: -r100 is faster and sufficient for web
+    let _permit = gs_limiter.acquire();
     let status = Command::new(gs_bin)
         .arg("-sDEVICE=png16m")
-        .arg("-r100")
+        .arg(format!("-r{}", opts.render_dpi))
         .arg("-dTextAlphaBits=4")
         .arg("-dGraphicsAlphaBits=4")
         .arg("-dNOPAUSE")
@@ -240,22 +781,305 @@ fn generate_images_from_pdf(pdf_path: &Path, full_out: &Path, thumb_out: &Path)
         .arg(pdf_path)
         .status()?;
 
+    drop(_permit);
+
     if !status.success() {
         anyhow::bail!("GS image render failed");
     }
 
-    // B. Convert to WebP using Rust
+    // B. Convert to the requested format(s) using Rust
     let img = image::open(&temp_png)?;
+    // Resize to the configured width, auto height (maintain aspect ratio)
+    let thumb = img.resize(opts.thumb_width, u32::MAX, FilterType::Lanczos3);
 
-    // Save Full WebP
-    img.save_with_format(full_out, image::ImageFormat::WebP)?;
+    for format in opts.image_formats {
+        let full_out = output_dir.join(format!("{}.{}", page_prefix, format.extension()));
+        let thumb_out = output_dir.join(format!("{}_thumb.{}", page_prefix, format.extension()));
+        save_image(&img, &full_out, *format, opts.image_quality)?;
+        save_image(&thumb, &thumb_out, *format, opts.image_quality)?;
+    }
 
-    // Save Thumbnail WebP
-    // Resize to fixed width 310px, auto height (maintain aspect ratio)
-    let thumb = img.resize(310, u32::MAX, FilterType::Lanczos3);
-    thumb.save_with_format(thumb_out, image::ImageFormat::WebP)?;
     // Cleanup
     let _ = fs::remove_file(temp_png);
 
     Ok(())
 }
+
+/// Encode `img` to `path` in the given format, using `quality` for the lossy encoders
+/// that support it (AVIF, JPEG) rather than the library-wide `save_with_format` default.
+fn save_image(
+    img: &image::DynamicImage,
+    path: &Path,
+    format: ImageFormatArg,
+    quality: u8,
+) -> Result<()> {
+    match format {
+        ImageFormatArg::Webp => img.save_with_format(path, image::ImageFormat::WebP)?,
+        ImageFormatArg::Png => img.save_with_format(path, image::ImageFormat::Png)?,
+        ImageFormatArg::Jpeg => {
+            let file = fs::File::create(path)?;
+            let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(file, quality);
+            img.write_with_encoder(encoder)?;
+        }
+        ImageFormatArg::Avif => {
+            let file = fs::File::create(path)?;
+            // Speed 4 is a reasonable default: noticeably faster than lossless-leaning
+            // speeds without the quality cliff of the very fastest settings.
+            let encoder =
+                image::codecs::avif::AvifEncoder::new_with_speed_quality(file, 4, quality);
+            img.write_with_encoder(encoder)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pdf_quality_maps_to_distinct_gs_args() {
+        assert_eq!(PdfQuality::Screen.as_gs_arg(), "-dPDFSETTINGS=/screen");
+        assert_eq!(PdfQuality::Ebook.as_gs_arg(), "-dPDFSETTINGS=/ebook");
+        assert_eq!(PdfQuality::Printer.as_gs_arg(), "-dPDFSETTINGS=/printer");
+        assert_eq!(PdfQuality::Prepress.as_gs_arg(), "-dPDFSETTINGS=/prepress");
+    }
+
+    #[test]
+    fn image_format_arg_maps_to_distinct_extensions() {
+        assert_eq!(ImageFormatArg::Webp.extension(), "webp");
+        assert_eq!(ImageFormatArg::Avif.extension(), "avif");
+        assert_eq!(ImageFormatArg::Jpeg.extension(), "jpg");
+        assert_eq!(ImageFormatArg::Png.extension(), "png");
+    }
+
+    #[test]
+    fn panic_message_extracts_str_payload() {
+        let payload: Box<dyn std::any::Any + Send> = Box::new("boom");
+        assert_eq!(panic_message(&payload), "boom");
+    }
+
+    #[test]
+    fn panic_message_extracts_string_payload() {
+        let payload: Box<dyn std::any::Any + Send> = Box::new(String::from("boom"));
+        assert_eq!(panic_message(&payload), "boom");
+    }
+
+    #[test]
+    fn panic_message_falls_back_for_unknown_payload() {
+        let payload: Box<dyn std::any::Any + Send> = Box::new(42i32);
+        assert_eq!(panic_message(&payload), "unknown panic");
+    }
+
+    /// Makes a fresh scratch directory under the system temp dir, named after the
+    /// calling test and the current time so parallel test runs don't collide.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("pdf-splitter-test-{}-{}", name, nanos));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Builds a minimal valid one-page PDF at `path`.
+    fn write_minimal_pdf(path: &Path) {
+        use lopdf::dictionary;
+
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+        });
+        doc.objects.insert(
+            pages_id,
+            lopdf::Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc.save(path).unwrap();
+    }
+
+    #[test]
+    fn compute_cache_hash_changes_with_settings() {
+        let dir = scratch_dir("hash-settings");
+        let pdf_path = dir.join("doc.pdf");
+        fs::write(&pdf_path, b"%PDF-1.4 fake content").unwrap();
+
+        let base = compute_cache_hash(&pdf_path, PdfQuality::Ebook, 150, 200, &[], 80).unwrap();
+        let different_dpi =
+            compute_cache_hash(&pdf_path, PdfQuality::Ebook, 300, 200, &[], 80).unwrap();
+        let same_again =
+            compute_cache_hash(&pdf_path, PdfQuality::Ebook, 150, 200, &[], 80).unwrap();
+
+        assert_ne!(base, different_dpi);
+        assert_eq!(base, same_again);
+    }
+
+    #[test]
+    fn compute_cache_hash_changes_with_file_contents() {
+        let dir = scratch_dir("hash-contents");
+        let pdf_path = dir.join("doc.pdf");
+
+        fs::write(&pdf_path, b"one").unwrap();
+        let first = compute_cache_hash(&pdf_path, PdfQuality::Screen, 150, 200, &[], 80).unwrap();
+        fs::write(&pdf_path, b"two").unwrap();
+        let second = compute_cache_hash(&pdf_path, PdfQuality::Screen, 150, 200, &[], 80).unwrap();
+
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn manifest_is_fresh_false_when_missing() {
+        let dir = scratch_dir("manifest-missing");
+        let manifest_path = dir.join("doc.manifest.json");
+        assert!(!manifest_is_fresh(&dir, &manifest_path, "somehash"));
+    }
+
+    #[test]
+    fn manifest_is_fresh_false_when_hash_differs() {
+        let dir = scratch_dir("manifest-hash-mismatch");
+        let manifest_path = dir.join("doc.manifest.json");
+        write_cache_manifest(&manifest_path, "old-hash", vec![]).unwrap();
+
+        assert!(!manifest_is_fresh(&dir, &manifest_path, "new-hash"));
+    }
+
+    #[test]
+    fn manifest_is_fresh_false_when_produced_file_missing() {
+        let dir = scratch_dir("manifest-produced-missing");
+        let manifest_path = dir.join("doc.manifest.json");
+        write_cache_manifest(&manifest_path, "hash", vec!["page-1.pdf".to_string()]).unwrap();
+
+        assert!(!manifest_is_fresh(&dir, &manifest_path, "hash"));
+    }
+
+    #[test]
+    fn manifest_is_fresh_true_when_hash_matches_and_files_present() {
+        let dir = scratch_dir("manifest-fresh");
+        let manifest_path = dir.join("doc.manifest.json");
+        fs::write(dir.join("page-1.pdf"), b"content").unwrap();
+        write_cache_manifest(&manifest_path, "hash", vec!["page-1.pdf".to_string()]).unwrap();
+
+        assert!(manifest_is_fresh(&dir, &manifest_path, "hash"));
+    }
+
+    #[test]
+    fn gs_limiter_zero_cap_is_unbounded() {
+        let limiter = GsLimiter::new(0);
+        let permits: Vec<_> = (0..8).map(|_| limiter.acquire()).collect();
+        assert_eq!(*limiter.available.lock().unwrap(), 0);
+        drop(permits);
+    }
+
+    #[test]
+    fn gs_limiter_tracks_available_permits() {
+        let limiter = GsLimiter::new(2);
+        assert_eq!(*limiter.available.lock().unwrap(), 2);
+
+        let first = limiter.acquire();
+        assert_eq!(*limiter.available.lock().unwrap(), 1);
+        let second = limiter.acquire();
+        assert_eq!(*limiter.available.lock().unwrap(), 0);
+
+        drop(first);
+        assert_eq!(*limiter.available.lock().unwrap(), 1);
+        drop(second);
+        assert_eq!(*limiter.available.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn gs_limiter_blocks_until_a_permit_is_released() {
+        let limiter = Arc::new(GsLimiter::new(1));
+        let first = limiter.acquire();
+
+        let waiter_limiter = Arc::clone(&limiter);
+        let acquired = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let waiter_acquired = Arc::clone(&acquired);
+        let handle = std::thread::spawn(move || {
+            let _second = waiter_limiter.acquire();
+            waiter_acquired.store(true, std::sync::atomic::Ordering::SeqCst);
+        });
+
+        // Give the spawned thread time to reach acquire(); while `first` is held it
+        // must still be waiting there.
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!acquired.load(std::sync::atomic::Ordering::SeqCst));
+        drop(first);
+
+        handle.join().unwrap();
+        assert!(acquired.load(std::sync::atomic::Ordering::SeqCst));
+        assert_eq!(*limiter.available.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn save_image_writes_a_non_empty_file_for_every_format() {
+        let dir = scratch_dir("save-image");
+        let img = image::DynamicImage::new_rgb8(4, 4);
+
+        for format in [
+            ImageFormatArg::Webp,
+            ImageFormatArg::Avif,
+            ImageFormatArg::Jpeg,
+            ImageFormatArg::Png,
+        ] {
+            let path = dir.join(format!("out.{}", format.extension()));
+            save_image(&img, &path, format, 80).unwrap();
+            let metadata =
+                fs::metadata(&path).unwrap_or_else(|_| panic!("{:?} was not written", format));
+            assert!(metadata.len() > 0, "{:?} was written empty", format);
+        }
+    }
+
+    #[test]
+    fn pdf_validation_is_healthy_requires_no_error_and_pages() {
+        let healthy = PdfValidation {
+            file: PathBuf::from("doc.pdf"),
+            page_count: 1,
+            encrypted: false,
+            error: None,
+        };
+        assert!(healthy.is_healthy());
+
+        let zero_pages = PdfValidation {
+            file: PathBuf::from("doc.pdf"),
+            page_count: 0,
+            encrypted: false,
+            error: None,
+        };
+        assert!(!zero_pages.is_healthy());
+
+        let errored = PdfValidation {
+            file: PathBuf::from("doc.pdf"),
+            page_count: 1,
+            encrypted: false,
+            error: Some("broken".to_string()),
+        };
+        assert!(!errored.is_healthy());
+    }
+
+    #[test]
+    fn run_preflight_validation_skips_only_unhealthy_files() {
+        let dir = scratch_dir("preflight");
+
+        let healthy_path = dir.join("healthy.pdf");
+        write_minimal_pdf(&healthy_path);
+
+        let broken_path = dir.join("broken.pdf");
+        fs::write(&broken_path, b"not a pdf").unwrap();
+
+        let skip = run_preflight_validation(&[healthy_path.clone(), broken_path.clone()]);
+
+        assert!(!skip.contains(&healthy_path));
+        assert!(skip.contains(&broken_path));
+    }
+}